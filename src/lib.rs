@@ -15,27 +15,82 @@
 //! });
 //!
 //! ```
+//!
+//! For code that wants to flush denormals for an entire scope instead of wrapping a single
+//! closure, [`disable_denormals`] returns the same guard that [`no_denormals`] uses internally.
+//! For code that sets the mode once (e.g. at thread startup) and never restores it,
+//! [`flush_denormals`] and [`keep_denormals`] are also provided.
+//!
+//! The rounding direction bits live in the same control register, so [`with_rounding_mode`]
+//! is provided as an RAII guard as well, for interval arithmetic or directed-rounding error
+//! bounds.
+//!
+//! LLVM doesn't model the floating point environment, so values a closure reads from outside
+//! itself can be constant-folded under the wrong rounding context; [`black_box_io`] closes
+//! that gap alongside the guards above.
+//!
+//! The same registers also carry the IEEE 754 exception mask and sticky status flags.
+//! [`with_exceptions_masked`] unmasks a chosen [`FpExceptions`] set for a closure, and
+//! [`read_raised_exceptions`] / [`clear_exceptions`] read and clear the sticky flags directly.
+//!
+//! ## Miri and unsupported targets
+//!
+//! The `ldmxcsr`/`stmxcsr` and `mrs`/`msr` inline asm used here cannot be interpreted by Miri,
+//! and isn't available on targets other than x86, x86_64 and aarch64. Under `cfg(miri)`, and on
+//! any other target, every guard and function in this crate becomes a no-op that just runs the
+//! closure, so downstream crates can depend on this one unconditionally and still run their
+//! test suite under Miri. Enable the `strict-hardware` feature to turn the unsupported-target
+//! case back into a hard compile error instead.
+//!
+//! ## `f16` tests
+//!
+//! [`no_denormals_f16`] and [`disable_denormals_f16`] are stable and build on stable Rust like
+//! the rest of the crate. Their tests, however, exercise the still-unstable `f16` type
+//! directly and only compile on nightly with `--features unstable-f16`; that feature is not
+//! enabled by default.
 
 #![warn(missing_docs, missing_debug_implementations, rust_2018_idioms)]
+#![cfg_attr(feature = "unstable-f16", feature(f16))]
 
 use core::marker::PhantomData;
+#[cfg(not(miri))]
+use core::sync::atomic::{compiler_fence, Ordering};
+#[cfg(not(miri))]
 use std::arch::asm;
 
-#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
-compile_error!("This crate only supports x86, x86_64 and aarch64.");
+#[cfg(all(
+	not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")),
+	feature = "strict-hardware"
+))]
+compile_error!(
+	"This crate only supports x86, x86_64 and aarch64. Disable the `strict-hardware` feature \
+	 to fall back to a no-op implementation on this target instead."
+);
 
 // FTZ and DAZ
-#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
 const X86_MASK: u32 = 0x8040;
 
 // FTZ
-#[cfg(target_arch = "aarch64")]
+#[cfg(all(not(miri), target_arch = "aarch64"))]
 const AARCH64_MASK: u64 = 1 << 24;
 
-struct DenormalGuard {
-	#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+// FZ16: flush-to-zero for half-precision (`f16`) arithmetic, independent of the FTZ bit
+// above, which only covers single and double precision.
+#[cfg(all(not(miri), target_arch = "aarch64"))]
+const AARCH64_MASK_F16: u64 = 1 << 19;
+
+/// RAII guard that restores the denormal handling flags to their previous state on drop.
+///
+/// Obtained from [`no_denormals`] or [`disable_denormals`]. The guard is `!Send` and `!Sync`
+/// since the flags it manages are local to the current thread.
+///
+/// Under Miri, or on a target other than x86, x86_64 and aarch64, this carries no state and
+/// dropping it does nothing; see the crate-level docs.
+pub struct DenormalGuard {
+	#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
 	mxcsr: u32,
-	#[cfg(target_arch = "aarch64")]
+	#[cfg(all(not(miri), target_arch = "aarch64"))]
 	fpcr: u64,
 
 	// These processor flags are local to each thread.
@@ -45,82 +100,671 @@ struct DenormalGuard {
 	_not_send_sync: PhantomData<*const ()>,
 }
 
+impl std::fmt::Debug for DenormalGuard {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("DenormalGuard").finish_non_exhaustive()
+	}
+}
+
+#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
 fn set_csr(control: u32) {
 	unsafe {
 		asm!("ldmxcsr [{}]", in(reg) &control);
 	}
 }
 
+#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
 fn get_csr() -> u32 {
-	let control: u32;
+	// `stmxcsr` stores through the address in the register operand, so it needs to point at
+	// an actual stack slot, not an uninitialized register.
+	let mut control: u32 = 0;
 	unsafe {
-		asm!("stmxcsr [{tmp}]",
-            "mov {x:e}, [{tmp}]",
-            x = out(reg) control,
-            tmp = out(reg) _)
+		asm!("stmxcsr [{}]", in(reg) &mut control);
 	}
 	control
 }
 
+#[cfg(all(not(miri), target_arch = "aarch64"))]
+fn get_fpcr() -> u64 {
+	let fpcr: u64;
+	unsafe { asm!("mrs {}, fpcr", out(reg) fpcr) };
+	fpcr
+}
+
+#[cfg(all(not(miri), target_arch = "aarch64"))]
+fn set_fpcr(fpcr: u64) {
+	unsafe { asm!("msr fpcr, {}", in(reg) fpcr) };
+}
+
+#[cfg(all(not(miri), target_arch = "aarch64"))]
+fn get_fpsr() -> u64 {
+	let fpsr: u64;
+	unsafe { asm!("mrs {}, fpsr", out(reg) fpsr) };
+	fpsr
+}
+
+#[cfg(all(not(miri), target_arch = "aarch64"))]
+fn set_fpsr(fpsr: u64) {
+	unsafe { asm!("msr fpsr, {}", in(reg) fpsr) };
+}
+
 impl DenormalGuard {
 	fn new() -> Self {
 		#[cfg(all(
+			not(miri),
 			any(target_arch = "x86_64", target_arch = "x86"),
 			target_feature = "sse"
 		))]
 		{
 			let mxcsr = get_csr();
 			set_csr(mxcsr | X86_MASK);
+			// LLVM doesn't model the floating point environment, so without this fence the
+			// optimizer is free to hoist floating point work from the closure above the
+			// `ldmxcsr` that just ran. The fence is a compile-time barrier only; it doesn't
+			// emit an instruction, but it stops the reorder.
+			compiler_fence(Ordering::SeqCst);
 
 			DenormalGuard {
 				mxcsr,
 				_not_send_sync: PhantomData,
 			}
 		}
-		#[cfg(target_arch = "aarch64")]
+		#[cfg(all(not(miri), target_arch = "aarch64"))]
 		{
-			let mut fpcr: u64;
-			unsafe { asm!("mrs {}, fpcr", out(reg) fpcr) };
-			unsafe { asm!("msr fpcr, {}", in(reg) fpcr | AARCH64_MASK) };
+			let fpcr = get_fpcr();
+			set_fpcr(fpcr | AARCH64_MASK);
+			compiler_fence(Ordering::SeqCst);
 
 			DenormalGuard {
 				fpcr,
 				_not_send_sync: PhantomData,
 			}
 		}
+		#[cfg(any(
+			miri,
+			not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))
+		))]
+		{
+			DenormalGuard {
+				_not_send_sync: PhantomData,
+			}
+		}
+	}
+
+	fn new_f16() -> Self {
+		#[cfg(all(
+			not(miri),
+			any(target_arch = "x86_64", target_arch = "x86"),
+			target_feature = "sse"
+		))]
+		{
+			// x86 has no separate flush bit for f16: the existing FTZ/DAZ flags already
+			// cover whatever half-float emulation maps onto SSE.
+			Self::new()
+		}
+		#[cfg(all(not(miri), target_arch = "aarch64"))]
+		{
+			let fpcr = get_fpcr();
+			set_fpcr(fpcr | AARCH64_MASK | AARCH64_MASK_F16);
+			compiler_fence(Ordering::SeqCst);
+
+			DenormalGuard {
+				fpcr,
+				_not_send_sync: PhantomData,
+			}
+		}
+		#[cfg(any(
+			miri,
+			not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))
+		))]
+		{
+			Self::new()
+		}
 	}
 }
 
 impl Drop for DenormalGuard {
 	fn drop(&mut self) {
 		#[cfg(all(
+			not(miri),
 			any(target_arch = "x86_64", target_arch = "x86"),
 			target_feature = "sse"
 		))]
 		{
+			// Symmetric fence: without it, floating point work from the closure could sink
+			// down past the restoring `ldmxcsr` below.
+			compiler_fence(Ordering::SeqCst);
 			set_csr(self.mxcsr);
 		}
 
-		#[cfg(target_arch = "aarch64")]
+		#[cfg(all(not(miri), target_arch = "aarch64"))]
 		{
-			unsafe { asm!("msr fpcr, {}", in(reg) self.fpcr) }
+			compiler_fence(Ordering::SeqCst);
+			set_fpcr(self.fpcr);
 		};
 	}
 }
 
-/// Calls the `func` closure.
+/// Calls `func` with `input`, routing both through [`core::hint::black_box`]: `input` right
+/// before the call, and `func`'s return value right after.
+///
+/// LLVM doesn't model the floating point environment at all, so without this, the compiler is
+/// free to constant-fold floating point work using whatever rounding context is in scope at
+/// compile time, which may not be the one [`no_denormals`], [`with_rounding_mode`] or
+/// [`with_exceptions_masked`] actually set up at runtime. Routing the return value through
+/// `black_box` (which all three of those already do internally) stops results computed
+/// *inside* the closure from being folded away; routing the inputs through `black_box` as
+/// well additionally stops values that flow in from *outside* the closure from being folded
+/// away before the guard ever takes effect:
+///
+/// ```rust
+/// use no_denormals::{black_box_io, no_denormals};
+///
+/// let a = 1.0f32;
+/// let b = f32::MIN_POSITIVE;
+/// let product = no_denormals(|| black_box_io((a, b), |(a, b)| a * b));
+/// ```
+#[inline]
+pub fn black_box_io<I, O, F: FnOnce(I) -> O>(input: I, func: F) -> O {
+	std::hint::black_box(func(std::hint::black_box(input)))
+}
+
+/// Calls the `func` closure with denormals flushed to zero, restoring the previous
+/// state afterwards.
+///
+/// The closure's return value is routed through [`core::hint::black_box`] before it is
+/// handed back to the caller. Without this, the compiler is free to constant-fold floating
+/// point work inside `func` using the *unflushed* rounding context (LLVM doesn't model the
+/// flushed-denormal environment at all), which would silently defeat the guard for any value
+/// that can be computed at compile time. `black_box` forces the computation to actually run
+/// under the modified flags. If `func` also reads values from outside the closure, route
+/// those through [`black_box_io`] as well.
 #[inline]
 pub fn no_denormals<T, F: FnOnce() -> T>(func: F) -> T {
 	let guard = DenormalGuard::new();
-	let ret = func();
+	let ret = std::hint::black_box(func());
+	std::mem::drop(guard);
+
+	ret
+}
+
+/// Disables denormals for as long as the returned [`DenormalGuard`] is alive, restoring the
+/// previous state when it is dropped.
+///
+/// This is useful when the scope to flush denormals for doesn't map neatly onto a single
+/// closure, e.g. for the whole duration of an audio processing callback.
+#[inline]
+pub fn disable_denormals() -> DenormalGuard {
+	DenormalGuard::new()
+}
+
+/// Calls the `func` closure with denormals flushed to zero, same as [`no_denormals`], but
+/// also flushes `f16` subnormals on aarch64.
+///
+/// aarch64 gates half-precision flush-to-zero behind its own FZ16 bit (FPCR bit 19), separate
+/// from the bit that covers single and double precision, so `f16` subnormals are not flushed
+/// by plain [`no_denormals`] on that architecture. On x86 and x86_64 this is identical to
+/// [`no_denormals`]: the existing FTZ/DAZ flags already cover whatever half-float emulation
+/// maps onto SSE.
+#[inline]
+pub fn no_denormals_f16<T, F: FnOnce() -> T>(func: F) -> T {
+	let guard = DenormalGuard::new_f16();
+	let ret = std::hint::black_box(func());
 	std::mem::drop(guard);
 
 	ret
 }
 
+/// Disables denormals for as long as the returned [`DenormalGuard`] is alive, same as
+/// [`disable_denormals`], but also flushes `f16` subnormals on aarch64.
+///
+/// See [`no_denormals_f16`] for details on the aarch64-specific FZ16 bit this sets.
+#[inline]
+pub fn disable_denormals_f16() -> DenormalGuard {
+	DenormalGuard::new_f16()
+}
+
+/// Sets the flush-to-zero and denormals-are-zero flags without any way to restore the
+/// previous state.
+///
+/// Intended for programs that want to set the mode once, e.g. at thread startup, rather than
+/// scoping it with [`no_denormals`] or [`disable_denormals`].
+#[inline]
+pub fn flush_denormals() {
+	#[cfg(all(
+		not(miri),
+		any(target_arch = "x86_64", target_arch = "x86"),
+		target_feature = "sse"
+	))]
+	set_csr(get_csr() | X86_MASK);
+
+	#[cfg(all(not(miri), target_arch = "aarch64"))]
+	set_fpcr(get_fpcr() | AARCH64_MASK);
+}
+
+/// Clears the flush-to-zero and denormals-are-zero flags, restoring standard IEEE 754
+/// denormal handling.
+///
+/// This is the counterpart to [`flush_denormals`] for programs that set the mode once
+/// rather than scoping it with a guard.
+#[inline]
+pub fn keep_denormals() {
+	#[cfg(all(
+		not(miri),
+		any(target_arch = "x86_64", target_arch = "x86"),
+		target_feature = "sse"
+	))]
+	set_csr(get_csr() & !X86_MASK);
+
+	#[cfg(all(not(miri), target_arch = "aarch64"))]
+	set_fpcr(get_fpcr() & !AARCH64_MASK);
+}
+
+/// IEEE 754 rounding direction, controlled via the same MXCSR (x86) or FPCR (aarch64)
+/// register that [`DenormalGuard`] manipulates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+	/// Round to nearest, ties to even. This is the IEEE 754 default.
+	NearestTiesEven,
+	/// Round toward negative infinity.
+	TowardNegative,
+	/// Round toward positive infinity.
+	TowardPositive,
+	/// Round toward zero.
+	TowardZero,
+}
+
+impl RoundingMode {
+	#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
+	fn bits(self) -> u32 {
+		match self {
+			RoundingMode::NearestTiesEven => 0b00,
+			RoundingMode::TowardNegative => 0b01,
+			RoundingMode::TowardPositive => 0b10,
+			RoundingMode::TowardZero => 0b11,
+		}
+	}
+
+	// aarch64's FPCR.RMode swaps the two directed-rounding encodings relative to x86's
+	// MXCSR.RC: 01 is toward +inf (RP) and 10 is toward -inf (RM), the other way around from
+	// x86, despite both otherwise following the same 00 = nearest, 11 = toward zero layout.
+	#[cfg(all(not(miri), target_arch = "aarch64"))]
+	fn bits(self) -> u32 {
+		match self {
+			RoundingMode::NearestTiesEven => 0b00,
+			RoundingMode::TowardPositive => 0b01,
+			RoundingMode::TowardNegative => 0b10,
+			RoundingMode::TowardZero => 0b11,
+		}
+	}
+}
+
+// Rounding control (RC) field.
+#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
+const X86_ROUNDING_SHIFT: u32 = 13;
+#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
+const X86_ROUNDING_MASK: u32 = 0b11 << X86_ROUNDING_SHIFT;
+
+// RMode field.
+#[cfg(all(not(miri), target_arch = "aarch64"))]
+const AARCH64_ROUNDING_SHIFT: u64 = 22;
+#[cfg(all(not(miri), target_arch = "aarch64"))]
+const AARCH64_ROUNDING_MASK: u64 = 0b11 << AARCH64_ROUNDING_SHIFT;
+
+/// RAII guard that restores the previous rounding mode on drop.
+///
+/// Obtained from [`with_rounding_mode`]. Like [`DenormalGuard`], this is `!Send` and
+/// `!Sync` since the flags it manages are local to the current thread.
+///
+/// Under Miri, or on a target other than x86, x86_64 and aarch64, this carries no state and
+/// dropping it does nothing; see the crate-level docs.
+pub struct RoundingModeGuard {
+	#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
+	mxcsr: u32,
+	#[cfg(all(not(miri), target_arch = "aarch64"))]
+	fpcr: u64,
+	_not_send_sync: PhantomData<*const ()>,
+}
+
+impl std::fmt::Debug for RoundingModeGuard {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("RoundingModeGuard").finish_non_exhaustive()
+	}
+}
+
+impl RoundingModeGuard {
+	fn new(mode: RoundingMode) -> Self {
+		#[cfg(all(
+			not(miri),
+			any(target_arch = "x86_64", target_arch = "x86"),
+			target_feature = "sse"
+		))]
+		{
+			let mxcsr = get_csr();
+			set_csr((mxcsr & !X86_ROUNDING_MASK) | (mode.bits() << X86_ROUNDING_SHIFT));
+			compiler_fence(Ordering::SeqCst);
+
+			RoundingModeGuard {
+				mxcsr,
+				_not_send_sync: PhantomData,
+			}
+		}
+		#[cfg(all(not(miri), target_arch = "aarch64"))]
+		{
+			let fpcr = get_fpcr();
+			let bits = u64::from(mode.bits());
+			set_fpcr((fpcr & !AARCH64_ROUNDING_MASK) | (bits << AARCH64_ROUNDING_SHIFT));
+			compiler_fence(Ordering::SeqCst);
+
+			RoundingModeGuard {
+				fpcr,
+				_not_send_sync: PhantomData,
+			}
+		}
+		#[cfg(any(
+			miri,
+			not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))
+		))]
+		{
+			let _ = mode;
+			RoundingModeGuard {
+				_not_send_sync: PhantomData,
+			}
+		}
+	}
+}
+
+impl Drop for RoundingModeGuard {
+	fn drop(&mut self) {
+		#[cfg(all(
+			not(miri),
+			any(target_arch = "x86_64", target_arch = "x86"),
+			target_feature = "sse"
+		))]
+		{
+			compiler_fence(Ordering::SeqCst);
+			// Restore the saved control bits, but keep the sticky status flags raised inside the
+			// closure.
+			let sticky = u32::from(FpExceptions::ALL.0);
+			set_csr((self.mxcsr & !sticky) | (get_csr() & sticky));
+		}
+
+		#[cfg(all(not(miri), target_arch = "aarch64"))]
+		{
+			compiler_fence(Ordering::SeqCst);
+			set_fpcr(self.fpcr);
+		};
+	}
+}
+
+/// Calls the `func` closure with `mode` as the active rounding direction, restoring the
+/// previous rounding mode afterwards.
+///
+/// This touches the same register as [`no_denormals`], so the two compose: nesting a
+/// `with_rounding_mode` call inside a `no_denormals` closure (or vice versa) only ever
+/// changes the bits each one owns. As with `no_denormals`, route any values `func` reads from
+/// outside the closure through [`black_box_io`] as well, or the compiler may constant-fold
+/// them under the wrong rounding context.
+#[inline]
+pub fn with_rounding_mode<T, F: FnOnce() -> T>(mode: RoundingMode, func: F) -> T {
+	let guard = RoundingModeGuard::new(mode);
+	let ret = std::hint::black_box(func());
+	std::mem::drop(guard);
+
+	ret
+}
+
+/// Bitset of the six IEEE 754 floating point exceptions, shared between the exception mask
+/// (x86 MXCSR bits 7-12, aarch64 FPCR enable bits) and the sticky status flags (x86 MXCSR
+/// bits 0-5, aarch64 FPSR status bits) that this crate's register plumbing already reads and
+/// writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FpExceptions(u8);
+
+impl FpExceptions {
+	/// No exceptions.
+	pub const NONE: Self = Self(0);
+	/// Invalid operation, e.g. `0.0 / 0.0` or the square root of a negative number.
+	pub const INVALID: Self = Self(1 << 0);
+	/// A denormal operand was used in a computation.
+	pub const DENORMAL: Self = Self(1 << 1);
+	/// Division by zero.
+	pub const DIVIDE_BY_ZERO: Self = Self(1 << 2);
+	/// The result overflowed the destination format.
+	pub const OVERFLOW: Self = Self(1 << 3);
+	/// The result underflowed the destination format.
+	pub const UNDERFLOW: Self = Self(1 << 4);
+	/// The result was rounded, and is therefore inexact.
+	pub const INEXACT: Self = Self(1 << 5);
+	/// All six exceptions.
+	pub const ALL: Self = Self(0b0011_1111);
+
+	/// Returns whether `self` has every flag set that's set in `other`.
+	pub fn contains(self, other: Self) -> bool {
+		self.0 & other.0 == other.0
+	}
+
+	/// Returns whether no flag is set.
+	pub fn is_empty(self) -> bool {
+		self.0 == 0
+	}
+}
+
+impl std::ops::BitOr for FpExceptions {
+	type Output = Self;
+	fn bitor(self, rhs: Self) -> Self {
+		Self(self.0 | rhs.0)
+	}
+}
+
+impl std::ops::BitOrAssign for FpExceptions {
+	fn bitor_assign(&mut self, rhs: Self) {
+		self.0 |= rhs.0;
+	}
+}
+
+impl std::ops::BitAnd for FpExceptions {
+	type Output = Self;
+	fn bitand(self, rhs: Self) -> Self {
+		Self(self.0 & rhs.0)
+	}
+}
+
+impl std::ops::Not for FpExceptions {
+	type Output = Self;
+	fn not(self) -> Self {
+		Self(!self.0 & Self::ALL.0)
+	}
+}
+
+// Exception mask (IM/DM/ZM/OM/UM/PM) field; shares the same six-bit order as `FpExceptions`.
+#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
+const X86_EXCEPTION_MASK_SHIFT: u32 = 7;
+
+// aarch64 scatters the exception enable bits (FPCR) and sticky status bits (FPSR) across
+// non-contiguous bits in an order that doesn't match `FpExceptions` or each other, unlike x86
+// where both fields share the same six-bit layout. Each entry is
+// (exception, FPCR enable-bit shift, FPSR sticky-bit shift).
+#[cfg(all(not(miri), target_arch = "aarch64"))]
+const AARCH64_EXCEPTION_BITS: [(FpExceptions, u32, u32); 6] = [
+	(FpExceptions::INVALID, 8, 0),
+	(FpExceptions::DIVIDE_BY_ZERO, 9, 1),
+	(FpExceptions::OVERFLOW, 10, 2),
+	(FpExceptions::UNDERFLOW, 11, 3),
+	(FpExceptions::INEXACT, 12, 4),
+	(FpExceptions::DENORMAL, 15, 7),
+];
+
+/// RAII guard that restores the previous exception mask on drop.
+///
+/// Obtained from [`with_exceptions_masked`]. Like [`DenormalGuard`], this is `!Send` and
+/// `!Sync` since the flags it manages are local to the current thread.
+///
+/// Under Miri, or on a target other than x86, x86_64 and aarch64, this carries no state and
+/// dropping it does nothing; see the crate-level docs.
+pub struct FpExceptionsGuard {
+	#[cfg(all(not(miri), any(target_arch = "x86", target_arch = "x86_64")))]
+	mxcsr: u32,
+	#[cfg(all(not(miri), target_arch = "aarch64"))]
+	fpcr: u64,
+	_not_send_sync: PhantomData<*const ()>,
+}
+
+impl std::fmt::Debug for FpExceptionsGuard {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("FpExceptionsGuard").finish_non_exhaustive()
+	}
+}
+
+impl FpExceptionsGuard {
+	fn new(unmask: FpExceptions) -> Self {
+		#[cfg(all(
+			not(miri),
+			any(target_arch = "x86_64", target_arch = "x86"),
+			target_feature = "sse"
+		))]
+		{
+			let mxcsr = get_csr();
+			let mask_bits = u32::from(unmask.0) << X86_EXCEPTION_MASK_SHIFT;
+			set_csr(mxcsr & !mask_bits);
+			compiler_fence(Ordering::SeqCst);
+
+			FpExceptionsGuard {
+				mxcsr,
+				_not_send_sync: PhantomData,
+			}
+		}
+		#[cfg(all(not(miri), target_arch = "aarch64"))]
+		{
+			let fpcr = get_fpcr();
+			let mut enable_bits = 0u64;
+			for (flag, fpcr_shift, _) in AARCH64_EXCEPTION_BITS {
+				if unmask.contains(flag) {
+					enable_bits |= 1 << fpcr_shift;
+				}
+			}
+			set_fpcr(fpcr | enable_bits);
+			compiler_fence(Ordering::SeqCst);
+
+			FpExceptionsGuard {
+				fpcr,
+				_not_send_sync: PhantomData,
+			}
+		}
+		#[cfg(any(
+			miri,
+			not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))
+		))]
+		{
+			let _ = unmask;
+			FpExceptionsGuard {
+				_not_send_sync: PhantomData,
+			}
+		}
+	}
+}
+
+impl Drop for FpExceptionsGuard {
+	fn drop(&mut self) {
+		#[cfg(all(
+			not(miri),
+			any(target_arch = "x86_64", target_arch = "x86"),
+			target_feature = "sse"
+		))]
+		{
+			compiler_fence(Ordering::SeqCst);
+			// Restore the saved exception mask, but keep the sticky status flags raised inside
+			// the closure.
+			let sticky = u32::from(FpExceptions::ALL.0);
+			set_csr((self.mxcsr & !sticky) | (get_csr() & sticky));
+		}
+
+		#[cfg(all(not(miri), target_arch = "aarch64"))]
+		{
+			compiler_fence(Ordering::SeqCst);
+			set_fpcr(self.fpcr);
+		};
+	}
+}
+
+/// Calls the `func` closure with the exceptions in `unmask` enabled (able to raise a hardware
+/// trap), restoring the previous exception mask afterwards.
+///
+/// Exceptions not in `unmask` are left exactly as they were: this only ever clears mask bits
+/// for the requested exceptions, it never masks an exception that was already unmasked. This
+/// touches the same register as [`no_denormals`] and [`with_rounding_mode`] and composes with
+/// both the same way, including needing [`black_box_io`] for any inputs `func` reads from
+/// outside the closure.
+#[inline]
+pub fn with_exceptions_masked<T, F: FnOnce() -> T>(unmask: FpExceptions, func: F) -> T {
+	let guard = FpExceptionsGuard::new(unmask);
+	let ret = std::hint::black_box(func());
+	std::mem::drop(guard);
+
+	ret
+}
+
+/// Reads which floating point exceptions have been raised (their sticky status flag is set)
+/// since the last [`clear_exceptions`] call.
+#[inline]
+pub fn read_raised_exceptions() -> FpExceptions {
+	#[cfg(all(
+		not(miri),
+		any(target_arch = "x86_64", target_arch = "x86"),
+		target_feature = "sse"
+	))]
+	{
+		FpExceptions((get_csr() & u32::from(FpExceptions::ALL.0)) as u8)
+	}
+	#[cfg(all(not(miri), target_arch = "aarch64"))]
+	{
+		let fpsr = get_fpsr();
+		let mut flags = FpExceptions::NONE;
+		for (flag, _, fpsr_shift) in AARCH64_EXCEPTION_BITS {
+			if fpsr & (1 << fpsr_shift) != 0 {
+				flags |= flag;
+			}
+		}
+		flags
+	}
+	#[cfg(any(
+		miri,
+		not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))
+	))]
+	{
+		FpExceptions::NONE
+	}
+}
+
+/// Clears every sticky floating point exception flag.
+#[inline]
+pub fn clear_exceptions() {
+	#[cfg(all(
+		not(miri),
+		any(target_arch = "x86_64", target_arch = "x86"),
+		target_feature = "sse"
+	))]
+	set_csr(get_csr() & !u32::from(FpExceptions::ALL.0));
+
+	#[cfg(all(not(miri), target_arch = "aarch64"))]
+	{
+		let mut sticky_mask = 0u64;
+		for (_, _, fpsr_shift) in AARCH64_EXCEPTION_BITS {
+			sticky_mask |= 1 << fpsr_shift;
+		}
+		set_fpsr(get_fpsr() & !sticky_mask);
+	}
+}
+
 #[cfg(test)]
 mod tests {
-	use crate::no_denormals;
+	use crate::{
+		black_box_io, clear_exceptions, disable_denormals, flush_denormals, keep_denormals,
+		no_denormals, read_raised_exceptions, with_exceptions_masked, with_rounding_mode,
+		FpExceptions, RoundingMode,
+	};
+	#[cfg(all(target_arch = "aarch64", feature = "unstable-f16"))]
+	use crate::no_denormals_f16;
 	use std::num::FpCategory;
 
 	fn half(x: f32) -> f32 {
@@ -165,4 +809,114 @@ mod tests {
 			assert_eq!(smaller.classify(), FpCategory::Subnormal);
 		};
 	}
+
+	#[test]
+	fn test_disable_denormals() {
+		let small: f32 = f32::MIN_POSITIVE;
+		{
+			let smaller = half(small);
+			assert_eq!(smaller.classify(), FpCategory::Subnormal);
+		}
+		{
+			let _guard = disable_denormals();
+			let smaller = half(small);
+			assert_eq!(smaller.classify(), FpCategory::Zero);
+		}
+		{
+			let smaller = half(small);
+			assert_eq!(smaller.classify(), FpCategory::Subnormal);
+		};
+	}
+
+	#[test]
+	fn test_flush_and_keep_denormals() {
+		let small: f32 = f32::MIN_POSITIVE;
+
+		flush_denormals();
+		let smaller = half(small);
+		assert_eq!(smaller.classify(), FpCategory::Zero);
+
+		keep_denormals();
+		let smaller = half(small);
+		assert_eq!(smaller.classify(), FpCategory::Subnormal);
+	}
+
+	#[test]
+	fn test_rounding_mode() {
+		// Exactly halfway between 1.0 and the next representable f32.
+		let a: f32 = 1.0;
+		let half_ulp: f32 = f32::EPSILON / 2.0;
+		let add = || std::hint::black_box(a) + std::hint::black_box(half_ulp);
+
+		let nearest = with_rounding_mode(RoundingMode::NearestTiesEven, add);
+		assert_eq!(nearest, 1.0);
+
+		let up = with_rounding_mode(RoundingMode::TowardPositive, add);
+		assert_eq!(up, 1.0 + f32::EPSILON);
+
+		let down = with_rounding_mode(RoundingMode::TowardNegative, add);
+		assert_eq!(down, 1.0);
+
+		let zero = with_rounding_mode(RoundingMode::TowardZero, add);
+		assert_eq!(zero, 1.0);
+
+		// Pin the raw FPCR.RMode encoding directly: it swaps the two directed-rounding bit
+		// patterns relative to x86's MXCSR.RC, so a regression here wouldn't necessarily show
+		// up as a behavioral difference if both bits happened to get swapped consistently.
+		#[cfg(target_arch = "aarch64")]
+		{
+			assert_eq!(RoundingMode::TowardPositive.bits(), 0b01);
+			assert_eq!(RoundingMode::TowardNegative.bits(), 0b10);
+		}
+	}
+
+	#[test]
+	fn test_exceptions_sticky_flags() {
+		clear_exceptions();
+		assert_eq!(read_raised_exceptions(), FpExceptions::NONE);
+
+		// The division's result has to be routed through `black_box_io` too: with nothing
+		// reading it, the compiler is free to treat it as a side-effect-free no-op (it doesn't
+		// model FP exception state) and elide the division entirely, so the sticky flag would
+		// never actually get set in hardware.
+		let zero: f32 = std::hint::black_box(0.0);
+		let _ = black_box_io(1.0f32, |n| n / zero);
+		assert!(read_raised_exceptions().contains(FpExceptions::DIVIDE_BY_ZERO));
+
+		clear_exceptions();
+		assert_eq!(read_raised_exceptions(), FpExceptions::NONE);
+	}
+
+	#[test]
+	fn test_with_exceptions_masked() {
+		// Exceptions stay masked by default, so leaving `unmask` empty only exercises the
+		// guard's save/restore path without risking a hardware trap.
+		let ret = with_exceptions_masked(FpExceptions::NONE, || 1.0f32 + 1.0);
+		assert_eq!(ret, 2.0);
+	}
+
+	// FZ16 is an aarch64-only control bit; on x86 half-float arithmetic doesn't go through
+	// MXCSR the same way, so there's nothing architecture-specific to assert there.
+	//
+	// `f16` itself is still unstable, so this only runs on nightly with `--features
+	// unstable-f16`; see the crate-level docs.
+	#[cfg(all(target_arch = "aarch64", feature = "unstable-f16"))]
+	#[test]
+	fn test_f16() {
+		let small: f16 = f16::MIN_POSITIVE;
+		let halved = || std::hint::black_box(small) / 2.0;
+
+		{
+			let smaller = halved();
+			assert_eq!(smaller.classify(), FpCategory::Subnormal);
+		}
+		no_denormals_f16(|| {
+			let smaller = halved();
+			assert_eq!(smaller.classify(), FpCategory::Zero);
+		});
+		{
+			let smaller = halved();
+			assert_eq!(smaller.classify(), FpCategory::Subnormal);
+		};
+	}
 }